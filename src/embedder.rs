@@ -2,6 +2,54 @@
 
 use eyre::Result;
 use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Downstream task an embedding is being produced for.
+///
+/// EmbeddingGemma is trained with task-specific instruction prefixes, so the
+/// text handed to the model must be wrapped differently depending on whether
+/// it is a query or a document, and what kind of task it will be used for.
+/// Picking the wrong prefix (or skipping it) noticeably hurts retrieval
+/// quality, so `embed_query`/`embed_documents` apply it automatically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmbedTask {
+    /// General search / retrieval (query-document matching)
+    #[default]
+    Retrieval,
+    /// Text classification
+    Classification,
+    /// Clustering of similar texts
+    Clustering,
+    /// Semantic textual similarity
+    SemanticSimilarity,
+    /// Code search / retrieval
+    CodeRetrieval,
+}
+
+impl EmbedTask {
+    /// Instruction prefix to prepend to a query for this task
+    fn query_prefix(&self) -> &'static str {
+        match self {
+            EmbedTask::Retrieval => "task: search result | query: ",
+            EmbedTask::Classification => "task: classification | query: ",
+            EmbedTask::Clustering => "task: clustering | query: ",
+            EmbedTask::SemanticSimilarity => "task: sentence similarity | query: ",
+            EmbedTask::CodeRetrieval => "task: code retrieval | query: ",
+        }
+    }
+
+    /// Instruction prefix to prepend to a document for this task
+    fn document_prefix(&self) -> &'static str {
+        match self {
+            EmbedTask::Retrieval => "title: none | text: ",
+            EmbedTask::Classification => "title: none | text: ",
+            EmbedTask::Clustering => "title: none | text: ",
+            EmbedTask::SemanticSimilarity => "title: none | text: ",
+            EmbedTask::CodeRetrieval => "title: none | text: ",
+        }
+    }
+}
 
 /// Quantization types for EmbeddingGemma models
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -43,9 +91,21 @@ impl QuantizationType {
     }
 }
 
+/// Native output dimension of EmbeddingGemma300M
+const NATIVE_DIMENSION: usize = 768;
+
+/// Output dimensions EmbeddingGemma's Matryoshka (MRL) training supports,
+/// including the native 768
+const SUPPORTED_MRL_DIMENSIONS: [usize; 4] = [768, 512, 256, 128];
+
 /// Text embedder wrapper around FastEmbed
 pub struct TextEmbedder {
     model: TextEmbedding,
+    output_dimension: usize,
+    batch_size: Option<usize>,
+    /// Built once by `with_threads`, reused by every `embed`/`embed_one`
+    /// call instead of spinning up a new OS thread pool per call.
+    thread_pool: Option<rayon::ThreadPool>,
 }
 
 impl TextEmbedder {
@@ -57,7 +117,12 @@ impl TextEmbedder {
         )
         .map_err(|e| eyre::eyre!("Failed to load model: {}", e))?;
 
-        Ok(TextEmbedder { model })
+        Ok(TextEmbedder {
+            model,
+            output_dimension: NATIVE_DIMENSION,
+            batch_size: None,
+            thread_pool: None,
+        })
     }
 
     /// Create a new TextEmbedder using EmbeddingGemma300M Q4F16 quantized model (auto-downloaded, ~175MB)
@@ -68,7 +133,12 @@ impl TextEmbedder {
         )
         .map_err(|e| eyre::eyre!("Failed to load quantized model: {}", e))?;
 
-        Ok(TextEmbedder { model })
+        Ok(TextEmbedder {
+            model,
+            output_dimension: NATIVE_DIMENSION,
+            batch_size: None,
+            thread_pool: None,
+        })
     }
 
     /// Create with a specific predefined model
@@ -77,24 +147,131 @@ impl TextEmbedder {
             TextEmbedding::try_new(InitOptions::new(model_type).with_show_download_progress(true))
                 .map_err(|e| eyre::eyre!("Failed to load model: {}", e))?;
 
-        Ok(TextEmbedder { model })
+        Ok(TextEmbedder {
+            model,
+            output_dimension: NATIVE_DIMENSION,
+            batch_size: None,
+            thread_pool: None,
+        })
     }
 
-    /// Get the embedding dimension (768 for EmbeddingGemma300M)
+    /// Truncate output embeddings to `dimension` components using EmbeddingGemma's
+    /// Matryoshka (MRL) training, trading accuracy for a smaller footprint.
+    ///
+    /// `dimension` must be one of the sizes EmbeddingGemma was trained to
+    /// support: 768 (native), 512, 256, or 128.
+    pub fn with_output_dimension(mut self, dimension: usize) -> Result<Self> {
+        if !SUPPORTED_MRL_DIMENSIONS.contains(&dimension) {
+            return Err(eyre::eyre!(
+                "unsupported MRL output dimension {}, expected one of {:?}",
+                dimension,
+                SUPPORTED_MRL_DIMENSIONS
+            ));
+        }
+        self.output_dimension = dimension;
+        Ok(self)
+    }
+
+    /// Get the configured embedding dimension (768 for EmbeddingGemma300M,
+    /// or the truncated MRL size set via `with_output_dimension`)
     pub fn dimension(&self) -> usize {
-        768
+        self.output_dimension
+    }
+
+    /// Split `embed` input into fixed-size batches fed to the model one at a
+    /// time, instead of handing FastEmbed the whole input in one call.
+    /// Smaller batches keep peak memory down on large inputs.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Result<Self> {
+        if batch_size == 0 {
+            return Err(eyre::eyre!("batch_size must be greater than 0"));
+        }
+        self.batch_size = Some(batch_size);
+        Ok(self)
+    }
+
+    /// Run independent batches across a dedicated rayon thread pool with
+    /// this many threads, instead of the global default pool. The pool is
+    /// built once, here, and reused across every `embed`/`embed_one` call.
+    /// Only takes effect together with `with_batch_size`, since a single
+    /// batch has nothing to parallelize.
+    pub fn with_threads(mut self, threads: usize) -> Result<Self> {
+        if threads == 0 {
+            return Err(eyre::eyre!("threads must be greater than 0"));
+        }
+        self.thread_pool = Some(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(|e| eyre::eyre!("Failed to build thread pool: {}", e))?,
+        );
+        Ok(self)
     }
 
     /// Generate embeddings for multiple texts
     pub fn embed(&mut self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        self.embed_chunked(texts, None)
+    }
+
+    /// Generate embeddings for multiple texts, invoking `on_progress(done,
+    /// total)` after each batch completes so callers can drive a progress
+    /// bar over large inputs.
+    pub fn embed_with_progress(
+        &mut self,
+        texts: Vec<String>,
+        on_progress: impl Fn(usize, usize) + Sync,
+    ) -> Result<Vec<Vec<f32>>> {
+        self.embed_chunked(texts, Some(&on_progress))
+    }
+
+    /// Shared implementation behind `embed`/`embed_with_progress`: chunk the
+    /// input per `batch_size`, embed each chunk (in parallel across
+    /// `threads` if configured), and reassemble results in original order.
+    fn embed_chunked(
+        &self,
+        texts: Vec<String>,
+        on_progress: Option<&(dyn Fn(usize, usize) + Sync)>,
+    ) -> Result<Vec<Vec<f32>>> {
         if texts.is_empty() {
             return Ok(vec![]);
         }
 
-        let embeddings = self
-            .model
-            .embed(texts, None)
-            .map_err(|e| eyre::eyre!("Embedding failed: {}", e))?;
+        let total = texts.len();
+        let batch_size = self.batch_size.unwrap_or(total);
+        let chunks: Vec<Vec<String>> = texts
+            .chunks(batch_size)
+            .map(|chunk| chunk.to_vec())
+            .collect();
+        let completed = AtomicUsize::new(0);
+
+        let embed_chunk = |chunk: Vec<String>| -> Result<Vec<Vec<f32>>> {
+            let chunk_len = chunk.len();
+            let result = self
+                .model
+                .embed(chunk, None)
+                .map_err(|e| eyre::eyre!("Embedding failed: {}", e))?;
+
+            let done = completed.fetch_add(chunk_len, Ordering::SeqCst) + chunk_len;
+            if let Some(on_progress) = on_progress {
+                on_progress(done, total);
+            }
+
+            Ok(result)
+        };
+
+        let chunked_results: Vec<Vec<Vec<f32>>> = if let Some(pool) = &self.thread_pool {
+            pool.install(|| chunks.into_par_iter().map(embed_chunk).collect())?
+        } else {
+            chunks
+                .into_iter()
+                .map(embed_chunk)
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        let embeddings = chunked_results
+            .into_iter()
+            .flatten()
+            .map(|embedding| truncate_and_normalize(embedding, self.output_dimension))
+            .collect();
 
         Ok(embeddings)
     }
@@ -107,4 +284,71 @@ impl TextEmbedder {
             .pop()
             .ok_or_else(|| eyre::eyre!("No embedding generated"))
     }
+
+    /// Embed a single query for retrieval against a document index
+    ///
+    /// Prepends the task-specific query instruction (e.g. `"task: search
+    /// result | query: "` for `EmbedTask::Retrieval`) before running the
+    /// model, matching how EmbeddingGemma was trained.
+    pub fn embed_query(&mut self, text: &str, task: EmbedTask) -> Result<Vec<f32>> {
+        let prefixed = format!("{}{}", task.query_prefix(), text);
+        self.embed_one(&prefixed)
+    }
+
+    /// Embed a batch of documents for retrieval
+    ///
+    /// Prepends the task-specific document instruction (e.g. `"title: none |
+    /// text: "`) to each text before running the model.
+    pub fn embed_documents(&mut self, texts: Vec<String>, task: EmbedTask) -> Result<Vec<Vec<f32>>> {
+        let prefixed = texts
+            .into_iter()
+            .map(|text| format!("{}{}", task.document_prefix(), text))
+            .collect();
+        self.embed(prefixed)
+    }
+}
+
+/// Slice an embedding to its first `dimension` components and re-normalize
+/// to unit L2 norm so cosine similarity over the truncated vector stays valid.
+///
+/// Leaves the embedding untouched when `dimension` is not smaller than its
+/// current length, so callers who never opt into MRL truncation keep
+/// FastEmbed's raw output byte-for-byte.
+fn truncate_and_normalize(mut embedding: Vec<f32>, dimension: usize) -> Vec<f32> {
+    if dimension >= embedding.len() {
+        return embedding;
+    }
+
+    embedding.truncate(dimension);
+
+    let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for x in embedding.iter_mut() {
+            *x /= norm;
+        }
+    }
+
+    embedding
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untruncated_embeddings_are_returned_byte_for_byte() {
+        let embedding = vec![3.0, -1.0, 4.0, 1.0, 5.0];
+        let output = truncate_and_normalize(embedding.clone(), embedding.len());
+        assert_eq!(output, embedding);
+    }
+
+    #[test]
+    fn truncated_embeddings_have_unit_l2_norm() {
+        let embedding = vec![3.0, -1.0, 4.0, 1.0, 5.0, 9.0, 2.0, 6.0];
+        let output = truncate_and_normalize(embedding, 4);
+
+        assert_eq!(output.len(), 4);
+        let norm = output.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-6, "norm was {norm}");
+    }
 }
@@ -0,0 +1,503 @@
+//! In-memory semantic search index combining dense retrieval with reranking
+
+use crate::embedder::{EmbedTask, TextEmbedder};
+use crate::quantize::QuantizedArray;
+use crate::reranker::TextReranker;
+use crate::storage;
+use eyre::Result;
+use std::path::Path;
+
+/// Default size of the dense-retrieval candidate pool handed to the reranker
+const DEFAULT_RERANK_POOL: usize = 50;
+
+struct IndexEntry {
+    id: usize,
+    text: String,
+    /// Raw embedding, kept around until `compress` replaces per-vector
+    /// storage with a shared `QuantizedArray` to cut RAM usage.
+    embedding: Option<Vec<f32>>,
+}
+
+/// A single search hit
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    /// Id assigned when the document was added via `SemanticIndex::add`
+    pub id: usize,
+    /// The original document text
+    pub text: String,
+    /// Cosine similarity between the query and document embeddings
+    pub dense_score: f32,
+    /// Score from `TextReranker`, if a reranker was configured
+    pub rerank_score: Option<f32>,
+    /// Calibrated `0..1` relevance score from `TextReranker`, if a reranker
+    /// configured with `with_calibration` was used
+    pub normalized_score: Option<f32>,
+}
+
+/// In-memory store of `(id, text, embedding)` triples with two-stage
+/// retrieve-then-rerank search, mirroring the flow callers previously had to
+/// wire up by hand from `TextEmbedder` and `TextReranker`.
+pub struct SemanticIndex {
+    embedder: TextEmbedder,
+    reranker: Option<TextReranker>,
+    entries: Vec<IndexEntry>,
+    /// Set once `compress` has trained codebooks over the current corpus;
+    /// `search` then scores through `QuantizedArray::distance_table`
+    /// instead of the (by then dropped) raw embeddings.
+    quantized: Option<QuantizedArray>,
+    next_id: usize,
+}
+
+impl SemanticIndex {
+    /// Create an index backed by the given embedder, with no reranking stage
+    pub fn new(embedder: TextEmbedder) -> Self {
+        SemanticIndex {
+            embedder,
+            reranker: None,
+            entries: Vec::new(),
+            quantized: None,
+            next_id: 0,
+        }
+    }
+
+    /// Attach a reranker, enabling the second-stage reranking pass in `search`
+    pub fn with_reranker(mut self, reranker: TextReranker) -> Self {
+        self.reranker = Some(reranker);
+        self
+    }
+
+    /// Embed and store a batch of documents, returning their assigned ids
+    ///
+    /// Fails once `compress` has been called: the trained codebooks cover
+    /// only the corpus that existed at that point, so growing the index
+    /// further requires rebuilding it from scratch.
+    pub fn add(&mut self, texts: Vec<String>) -> Result<Vec<usize>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+        if self.quantized.is_some() {
+            return Err(eyre::eyre!(
+                "cannot add documents after compress(); rebuild the index to add more data"
+            ));
+        }
+
+        let embeddings = self
+            .embedder
+            .embed_documents(texts.clone(), EmbedTask::Retrieval)?;
+
+        let mut ids = Vec::with_capacity(texts.len());
+        for (text, embedding) in texts.into_iter().zip(embeddings) {
+            let id = self.next_id;
+            self.next_id += 1;
+            self.entries.push(IndexEntry {
+                id,
+                text,
+                embedding: Some(embedding),
+            });
+            ids.push(id);
+        }
+
+        Ok(ids)
+    }
+
+    /// Compress the currently stored embeddings into a product-quantized
+    /// `QuantizedArray` (see `QuantizedArray::quantize`), and drop the raw
+    /// per-vector storage so the index holds `subspaces` bytes per document
+    /// plus the shared codebooks instead of `4 * dimension` bytes per
+    /// document. Codebooks are trained on a bounded sample of the corpus
+    /// (see `QuantizedArray::quantize`'s default sample size); use
+    /// `compress_with_sample` to override it.
+    ///
+    /// Degrades `search`'s dense-retrieval stage to the quantizer's
+    /// asymmetric cosine approximation; reranking (if configured) still
+    /// runs against the original document text, so final ranking quality
+    /// is largely unaffected.
+    pub fn compress(&mut self, subspaces: usize) -> Result<()> {
+        self.quantize_entries(subspaces, None)
+    }
+
+    /// Like `compress`, but caps codebook training at `sample_size`
+    /// embeddings instead of the default. Useful once the corpus is large
+    /// enough that training over every stored embedding would be slow.
+    pub fn compress_with_sample(&mut self, subspaces: usize, sample_size: usize) -> Result<()> {
+        self.quantize_entries(subspaces, Some(sample_size))
+    }
+
+    fn quantize_entries(&mut self, subspaces: usize, sample_size: Option<usize>) -> Result<()> {
+        let embeddings: Vec<Vec<f32>> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                entry
+                    .embedding
+                    .clone()
+                    .ok_or_else(|| eyre::eyre!("index is already compressed"))
+            })
+            .collect::<Result<_>>()?;
+
+        self.quantized = Some(match sample_size {
+            Some(sample_size) => {
+                QuantizedArray::quantize_with_sample(&embeddings, subspaces, sample_size)?
+            }
+            None => QuantizedArray::quantize(&embeddings, subspaces)?,
+        });
+        for entry in &mut self.entries {
+            entry.embedding = None;
+        }
+
+        Ok(())
+    }
+
+    /// Number of documents currently stored in the index
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the index holds no documents
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Embed `query`, score every stored document by cosine similarity
+    /// (approximated via the quantizer's distance table once `compress` has
+    /// been called), and return the top `top_k` results. If a reranker is
+    /// configured, the top `DEFAULT_RERANK_POOL` (or `top_k`, if larger)
+    /// dense candidates are reranked first and the final ranking follows the reranker's scores
+    /// instead.
+    pub fn search(&mut self, query: &str, top_k: usize) -> Result<Vec<SearchResult>> {
+        if self.entries.is_empty() || top_k == 0 {
+            return Ok(vec![]);
+        }
+
+        let query_embedding = self.embedder.embed_query(query, EmbedTask::Retrieval)?;
+
+        let mut scored: Vec<(usize, f32)> = if let Some(quantized) = &self.quantized {
+            let table = quantized.distance_table(&query_embedding)?;
+            (0..self.entries.len())
+                .map(|idx| (idx, table.cosine(quantized.code(idx))))
+                .collect()
+        } else {
+            self.entries
+                .iter()
+                .enumerate()
+                .map(|(idx, entry)| {
+                    let embedding = entry
+                        .embedding
+                        .as_ref()
+                        .expect("dense entry is missing its embedding");
+                    (idx, cosine_similarity(&query_embedding, embedding))
+                })
+                .collect()
+        };
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let Some(reranker) = self.reranker.as_mut() else {
+            return Ok(scored
+                .into_iter()
+                .take(top_k)
+                .map(|(idx, dense_score)| {
+                    let entry = &self.entries[idx];
+                    SearchResult {
+                        id: entry.id,
+                        text: entry.text.clone(),
+                        dense_score,
+                        rerank_score: None,
+                        normalized_score: None,
+                    }
+                })
+                .collect());
+        };
+
+        let pool_size = DEFAULT_RERANK_POOL.max(top_k).min(scored.len());
+        let pool = &scored[..pool_size];
+
+        let pool_texts: Vec<String> = pool
+            .iter()
+            .map(|(idx, _)| self.entries[*idx].text.clone())
+            .collect();
+        let rerank_results = reranker.rerank(query.to_string(), pool_texts)?;
+
+        let mut results: Vec<SearchResult> = rerank_results
+            .into_iter()
+            .map(|r| {
+                let (idx, dense_score) = pool[r.index];
+                let entry = &self.entries[idx];
+                SearchResult {
+                    id: entry.id,
+                    text: entry.text.clone(),
+                    dense_score,
+                    rerank_score: Some(r.score),
+                    normalized_score: r.normalized_score,
+                }
+            })
+            .collect();
+
+        results.truncate(top_k);
+        Ok(results)
+    }
+
+    /// Persist every stored document (text + embedding) to `path` so it can
+    /// be reloaded without re-embedding the corpus. Numeric ids are not
+    /// preserved; `load` reassigns sequential ids in the saved order.
+    ///
+    /// Requires a non-compressed index; call this before `compress` if you
+    /// need both a persisted copy and an in-memory quantized one.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let texts: Vec<String> = self.entries.iter().map(|e| e.text.clone()).collect();
+        let vectors: Vec<Vec<f32>> = self
+            .entries
+            .iter()
+            .map(|e| {
+                e.embedding
+                    .clone()
+                    .ok_or_else(|| eyre::eyre!("cannot save a compressed index"))
+            })
+            .collect::<Result<_>>()?;
+        storage::save_embeddings(path, &texts, &vectors)
+    }
+
+    /// Rebuild an index from a file written by `save`, reusing `embedder`
+    /// for future `search` queries.
+    pub fn load(embedder: TextEmbedder, path: impl AsRef<Path>) -> Result<Self> {
+        let (texts, vectors) = storage::load_embeddings(path)?;
+
+        let mut index = SemanticIndex::new(embedder);
+        for (text, embedding) in texts.into_iter().zip(vectors) {
+            let id = index.next_id;
+            index.next_id += 1;
+            index.entries.push(IndexEntry {
+                id,
+                text,
+                embedding: Some(embedding),
+            });
+        }
+
+        Ok(index)
+    }
+
+    /// Persist a `compress`-ed index's trained codebooks and per-document
+    /// codes to `path`, so a subsequent `load_quantized` can reload it
+    /// without retraining. Numeric ids are not preserved; `load_quantized`
+    /// reassigns sequential ids in the saved order.
+    pub fn save_quantized(&self, path: impl AsRef<Path>) -> Result<()> {
+        let quantized = self
+            .quantized
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("index is not compressed; call compress() first"))?;
+        let texts: Vec<String> = self.entries.iter().map(|e| e.text.clone()).collect();
+        storage::save_quantized(path, &texts, quantized)
+    }
+
+    /// Rebuild a previously `compress`-ed index from a file written by
+    /// `save_quantized`, reusing `embedder` for future `search` queries.
+    /// Unlike `load`, no exact dense scoring over raw embeddings is possible
+    /// for the restored entries since only the quantized codes are stored; `search`
+    /// falls back to the quantizer's asymmetric cosine approximation just
+    /// as it does right after `compress`.
+    pub fn load_quantized(embedder: TextEmbedder, path: impl AsRef<Path>) -> Result<Self> {
+        let (texts, quantized) = storage::load_quantized(path)?;
+
+        let mut index = SemanticIndex::new(embedder);
+        for text in texts {
+            let id = index.next_id;
+            index.next_id += 1;
+            index.entries.push(IndexEntry {
+                id,
+                text,
+                embedding: None,
+            });
+        }
+        index.quantized = Some(quantized);
+
+        Ok(index)
+    }
+}
+
+/// Cosine similarity between two equal-length vectors
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fake index whose `add` takes each text together with a
+    /// caller-supplied embedding, so index tests can exercise
+    /// `SemanticIndex`'s logic without downloading an ONNX model.
+    ///
+    /// `TextEmbedder` itself wraps a concrete `fastembed::TextEmbedding`
+    /// with no trait seam, so these tests build a tiny `SemanticIndex`
+    /// substitute over the same entry/search logic instead of going
+    /// through the real struct.
+    struct FakeIndex {
+        entries: Vec<(usize, String, Option<Vec<f32>>)>,
+        quantized: Option<QuantizedArray>,
+        next_id: usize,
+    }
+
+    impl FakeIndex {
+        fn new() -> Self {
+            FakeIndex {
+                entries: Vec::new(),
+                quantized: None,
+                next_id: 0,
+            }
+        }
+
+        fn add(&mut self, text: &str, embedding: Vec<f32>) -> usize {
+            self.try_add(text, embedding).unwrap()
+        }
+
+        /// Mirrors `SemanticIndex::add`'s guard: once `compress` has run,
+        /// further additions are rejected rather than silently accepted
+        /// into a corpus the codebooks were never trained on.
+        fn try_add(&mut self, text: &str, embedding: Vec<f32>) -> Result<usize> {
+            if self.quantized.is_some() {
+                return Err(eyre::eyre!(
+                    "cannot add documents after compress(); rebuild the index to add more data"
+                ));
+            }
+            let id = self.next_id;
+            self.next_id += 1;
+            self.entries
+                .push((id, text.to_string(), Some(embedding)));
+            Ok(id)
+        }
+
+        fn compress(&mut self, subspaces: usize) -> Result<()> {
+            let embeddings: Vec<Vec<f32>> = self
+                .entries
+                .iter()
+                .map(|(_, _, e)| e.clone().ok_or_else(|| eyre::eyre!("already compressed")))
+                .collect::<Result<_>>()?;
+            self.quantized = Some(QuantizedArray::quantize(&embeddings, subspaces)?);
+            for entry in &mut self.entries {
+                entry.2 = None;
+            }
+            Ok(())
+        }
+
+        fn search(&self, query: &[f32], top_k: usize) -> Vec<(usize, String, f32)> {
+            let mut scored: Vec<(usize, f32)> = if let Some(quantized) = &self.quantized {
+                let table = quantized.distance_table(query).unwrap();
+                (0..self.entries.len())
+                    .map(|idx| (idx, table.cosine(quantized.code(idx))))
+                    .collect()
+            } else {
+                self.entries
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, (_, _, e))| {
+                        (idx, cosine_similarity(query, e.as_ref().unwrap()))
+                    })
+                    .collect()
+            };
+            scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+            scored
+                .into_iter()
+                .take(top_k)
+                .map(|(idx, score)| {
+                    let (id, text, _) = &self.entries[idx];
+                    (*id, text.clone(), score)
+                })
+                .collect()
+        }
+    }
+
+    fn corpus() -> Vec<(&'static str, Vec<f32>)> {
+        vec![
+            ("cats", vec![1.0, 0.0, 0.0]),
+            ("dogs", vec![0.9, 0.1, 0.0]),
+            ("rockets", vec![0.0, 0.0, 1.0]),
+        ]
+    }
+
+    #[test]
+    fn dense_search_ranks_by_cosine_similarity() {
+        let mut index = FakeIndex::new();
+        for (text, embedding) in corpus() {
+            index.add(text, embedding);
+        }
+
+        let results = index.search(&[1.0, 0.0, 0.0], 3);
+        let texts: Vec<&str> = results.iter().map(|(_, t, _)| t.as_str()).collect();
+        assert_eq!(texts, vec!["cats", "dogs", "rockets"]);
+    }
+
+    #[test]
+    fn compress_then_search_uses_the_quantized_distance_table() {
+        let mut index = FakeIndex::new();
+        for (text, embedding) in corpus() {
+            index.add(text, embedding);
+        }
+        index.compress(1).unwrap();
+
+        let results = index.search(&[1.0, 0.0, 0.0], 1);
+        assert_eq!(results[0].1, "cats");
+    }
+
+    #[test]
+    fn add_after_compress_errors() {
+        let mut index = FakeIndex::new();
+        for (text, embedding) in corpus() {
+            index.add(text, embedding);
+        }
+        index.compress(1).unwrap();
+
+        let err = index.try_add("more", vec![1.0, 0.0, 0.0]).unwrap_err();
+        assert!(err.to_string().contains("compress"));
+    }
+
+    #[test]
+    fn save_and_load_quantized_round_trip_preserves_codes() {
+        let path = std::env::temp_dir().join(format!(
+            "embedding-gemma-rs-index-quantized-test-{}.bin",
+            std::process::id()
+        ));
+        let ids: Vec<String> = vec!["cats".into(), "dogs".into(), "rockets".into()];
+        let vectors: Vec<Vec<f32>> = corpus().into_iter().map(|(_, e)| e).collect();
+        let quantized = QuantizedArray::quantize(&vectors, 1).unwrap();
+
+        storage::save_quantized(&path, &ids, &quantized).unwrap();
+        let (loaded_ids, loaded_quantized) = storage::load_quantized(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded_ids, ids);
+        for i in 0..vectors.len() {
+            assert_eq!(loaded_quantized.code(i), quantized.code(i));
+        }
+    }
+
+    #[test]
+    fn save_and_load_round_trip_preserves_texts_and_search_order() {
+        let path = std::env::temp_dir().join(format!(
+            "embedding-gemma-rs-index-test-{}.bin",
+            std::process::id()
+        ));
+        let ids: Vec<String> = vec!["cats".into(), "dogs".into(), "rockets".into()];
+        let vectors: Vec<Vec<f32>> = corpus().into_iter().map(|(_, e)| e).collect();
+
+        storage::save_embeddings(&path, &ids, &vectors).unwrap();
+        let (loaded_ids, loaded_vectors) = storage::load_embeddings(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded_ids, ids);
+
+        let mut index = FakeIndex::new();
+        for (text, embedding) in loaded_ids.into_iter().zip(loaded_vectors) {
+            index.add(&text, embedding);
+        }
+
+        let results = index.search(&[1.0, 0.0, 0.0], 3);
+        let texts: Vec<&str> = results.iter().map(|(_, t, _)| t.as_str()).collect();
+        assert_eq!(texts, vec!["cats", "dogs", "rockets"]);
+    }
+}
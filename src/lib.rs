@@ -4,10 +4,16 @@
 //! using the EmbeddingGemma300M model (supports 100+ languages including Hindi/Hinglish).
 
 mod embedder;
+mod index;
+mod quantize;
 mod reranker;
+mod storage;
 
-pub use embedder::{QuantizationType, TextEmbedder};
-pub use reranker::TextReranker;
+pub use embedder::{EmbedTask, QuantizationType, TextEmbedder};
+pub use index::{SearchResult, SemanticIndex};
+pub use quantize::{DistanceTable, QuantizedArray};
+pub use reranker::{RerankedResult, ScoreCalibration, TextReranker};
+pub use storage::{load_embeddings, load_quantized, save_embeddings, save_quantized};
 
 #[cfg(feature = "python")]
 use pyo3::exceptions::PyRuntimeError;
@@ -44,6 +50,36 @@ impl From<PyQuantizationType> for QuantizationType {
     }
 }
 
+/// Python enum for task-specific instruction prefixes
+#[cfg(feature = "python")]
+#[pyclass(name = "EmbedTask", eq, eq_int)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PyEmbedTask {
+    /// General search / retrieval (query-document matching)
+    Retrieval = 0,
+    /// Text classification
+    Classification = 1,
+    /// Clustering of similar texts
+    Clustering = 2,
+    /// Semantic textual similarity
+    SemanticSimilarity = 3,
+    /// Code search / retrieval
+    CodeRetrieval = 4,
+}
+
+#[cfg(feature = "python")]
+impl From<PyEmbedTask> for EmbedTask {
+    fn from(py_task: PyEmbedTask) -> Self {
+        match py_task {
+            PyEmbedTask::Retrieval => EmbedTask::Retrieval,
+            PyEmbedTask::Classification => EmbedTask::Classification,
+            PyEmbedTask::Clustering => EmbedTask::Clustering,
+            PyEmbedTask::SemanticSimilarity => EmbedTask::SemanticSimilarity,
+            PyEmbedTask::CodeRetrieval => EmbedTask::CodeRetrieval,
+        }
+    }
+}
+
 #[cfg(feature = "python")]
 #[pyclass(name = "TextEmbedder")]
 pub struct PyTextEmbedder {
@@ -56,28 +92,48 @@ impl PyTextEmbedder {
     /// Create a new TextEmbedder using EmbeddingGemma full model (auto-downloaded, ~1.2GB)
     ///
     /// The model will be automatically downloaded on first use.
+    /// Args:
+    ///     output_dimension: Optional MRL truncation size (768, 512, 256, or 128)
+    ///     batch_size: Optional fixed batch size for chunked embedding
+    ///     threads: Optional rayon thread pool size for parallel batches
     #[new]
-    fn new() -> PyResult<Self> {
+    #[pyo3(signature = (output_dimension=None, batch_size=None, threads=None))]
+    fn new(
+        output_dimension: Option<usize>,
+        batch_size: Option<usize>,
+        threads: Option<usize>,
+    ) -> PyResult<Self> {
         let inner = TextEmbedder::new().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
                 "Failed to create embedder: {}",
                 e
             ))
         })?;
+        let inner = configure_embedder(inner, output_dimension, batch_size, threads)?;
         Ok(PyTextEmbedder { inner })
     }
 
     /// Create a new TextEmbedder using EmbeddingGemma Q4F16 quantized model (auto-downloaded, ~175MB)
     ///
     /// Recommended for low-end CPUs. Same quality, faster inference, smaller download.
+    /// Args:
+    ///     output_dimension: Optional MRL truncation size (768, 512, 256, or 128)
+    ///     batch_size: Optional fixed batch size for chunked embedding
+    ///     threads: Optional rayon thread pool size for parallel batches
     #[staticmethod]
-    fn new_quantized() -> PyResult<Self> {
+    #[pyo3(signature = (output_dimension=None, batch_size=None, threads=None))]
+    fn new_quantized(
+        output_dimension: Option<usize>,
+        batch_size: Option<usize>,
+        threads: Option<usize>,
+    ) -> PyResult<Self> {
         let inner = TextEmbedder::new_quantized_auto().map_err(|e| {
             PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
                 "Failed to create quantized embedder: {}",
                 e
             ))
         })?;
+        let inner = configure_embedder(inner, output_dimension, batch_size, threads)?;
         Ok(PyTextEmbedder { inner })
     }
 
@@ -100,12 +156,85 @@ impl PyTextEmbedder {
         })
     }
 
+    /// Embed a single query for retrieval against a document index
+    /// Args:
+    ///     text: Query string
+    ///     task: EmbedTask to select the instruction prefix (defaults to Retrieval)
+    #[pyo3(signature = (text, task=PyEmbedTask::Retrieval))]
+    fn embed_query(&mut self, text: String, task: PyEmbedTask) -> PyResult<Vec<f32>> {
+        self.inner.embed_query(&text, task.into()).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Embedding failed: {}", e))
+        })
+    }
+
+    /// Embed a batch of documents for retrieval
+    /// Args:
+    ///     texts: List of document strings
+    ///     task: EmbedTask to select the instruction prefix (defaults to Retrieval)
+    #[pyo3(signature = (texts, task=PyEmbedTask::Retrieval))]
+    fn embed_documents(&mut self, texts: Vec<String>, task: PyEmbedTask) -> PyResult<Vec<Vec<f32>>> {
+        self.inner.embed_documents(texts, task.into()).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Embedding failed: {}", e))
+        })
+    }
+
+    /// Generate embeddings for a list of texts, calling `on_progress(done,
+    /// total)` after each batch so Python callers can drive a progress bar.
+    /// Args:
+    ///     texts: List of strings to embed
+    ///     on_progress: Callable invoked with (completed_count, total_count)
+    fn embed_with_progress(
+        &mut self,
+        texts: Vec<String>,
+        on_progress: PyObject,
+    ) -> PyResult<Vec<Vec<f32>>> {
+        self.inner
+            .embed_with_progress(texts, |done, total| {
+                Python::with_gil(|py| {
+                    if let Err(e) = on_progress.call1(py, (done, total)) {
+                        e.print(py);
+                    }
+                });
+            })
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Embedding failed: {}",
+                    e
+                ))
+            })
+    }
+
     /// Get the embedding dimension (768 for EmbeddingGemma300M)
     fn dimension(&self) -> usize {
         self.inner.dimension()
     }
 }
 
+#[cfg(feature = "python")]
+fn configure_embedder(
+    mut embedder: TextEmbedder,
+    output_dimension: Option<usize>,
+    batch_size: Option<usize>,
+    threads: Option<usize>,
+) -> PyResult<TextEmbedder> {
+    if let Some(dimension) = output_dimension {
+        embedder = embedder
+            .with_output_dimension(dimension)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    }
+    if let Some(batch_size) = batch_size {
+        embedder = embedder
+            .with_batch_size(batch_size)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    }
+    if let Some(threads) = threads {
+        embedder = embedder
+            .with_threads(threads)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+    }
+    Ok(embedder)
+}
+
 #[cfg(feature = "python")]
 #[pyclass]
 struct Reranker {
@@ -115,17 +244,49 @@ struct Reranker {
 #[cfg(feature = "python")]
 #[pymethods]
 impl Reranker {
+    /// Args:
+    ///     mean: Optional mean for shifted-sigmoid score normalization
+    ///     sigma: Optional sigma for shifted-sigmoid score normalization
+    ///
+    /// `mean` and `sigma` must both be provided or both omitted; passing
+    /// only one is rejected rather than silently disabling calibration.
     #[new]
-    fn new() -> PyResult<Self> {
-        let inner = TextReranker::new().map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    #[pyo3(signature = (mean=None, sigma=None))]
+    fn new(mean: Option<f32>, sigma: Option<f32>) -> PyResult<Self> {
+        let mut inner =
+            TextReranker::new().map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        match (mean, sigma) {
+            (Some(mean), Some(sigma)) => {
+                inner = inner.with_calibration(mean, sigma);
+            }
+            (None, None) => {}
+            _ => {
+                return Err(PyRuntimeError::new_err(
+                    "mean and sigma must both be provided, or neither",
+                ));
+            }
+        }
         Ok(Reranker { inner })
     }
 
+    /// Estimate (mean, sigma) from a sample of raw reranker scores, for use
+    /// with `Reranker(mean=..., sigma=...)`.
+    #[staticmethod]
+    fn estimate_calibration(scores: Vec<f32>) -> PyResult<(f32, f32)> {
+        TextReranker::estimate_calibration(&scores)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Rerank a list of documents against a query
+    ///
+    /// Returns a list of `(document, raw_score, index, normalized_score)`
+    /// tuples; `normalized_score` is `None` unless this reranker was built
+    /// with `mean`/`sigma` calibration.
     fn rerank(
         &mut self,
         query: String,
         documents: Vec<String>,
-    ) -> PyResult<Vec<(String, f32, usize)>> {
+    ) -> PyResult<Vec<(String, f32, usize, Option<f32>)>> {
         let results = self
             .inner
             .rerank(query, documents)
@@ -133,13 +294,81 @@ impl Reranker {
 
         let py_results = results
             .into_iter()
-            .map(|r| (r.document.unwrap_or_default(), r.score, r.index))
+            .map(|r| (r.document.unwrap_or_default(), r.score, r.index, r.normalized_score))
             .collect();
 
         Ok(py_results)
     }
 }
 
+#[cfg(feature = "python")]
+#[pyclass(name = "SemanticIndex")]
+struct PySemanticIndex {
+    inner: SemanticIndex,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PySemanticIndex {
+    /// Create a new hybrid search index, embedding with EmbeddingGemma
+    /// (full model) and reranking with BGE-Reranker-V2-M3.
+    ///
+    /// Both models are automatically downloaded on first use.
+    #[new]
+    fn new() -> PyResult<Self> {
+        let embedder =
+            TextEmbedder::new().map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        let reranker =
+            TextReranker::new().map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        Ok(PySemanticIndex {
+            inner: SemanticIndex::new(embedder).with_reranker(reranker),
+        })
+    }
+
+    /// Embed and store a batch of documents, returning their assigned ids
+    fn add(&mut self, texts: Vec<String>) -> PyResult<Vec<usize>> {
+        self.inner
+            .add(texts)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Hybrid search: dense retrieval over the index followed by reranking
+    ///
+    /// Returns a list of `(id, text, dense_score, rerank_score,
+    /// normalized_score)` tuples. `normalized_score` is `None` unless the
+    /// configured reranker was built with score calibration.
+    fn search(
+        &mut self,
+        query: String,
+        top_k: usize,
+    ) -> PyResult<Vec<(usize, String, f32, Option<f32>, Option<f32>)>> {
+        let results = self
+            .inner
+            .search(&query, top_k)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+
+        Ok(results
+            .into_iter()
+            .map(|r| (r.id, r.text, r.dense_score, r.rerank_score, r.normalized_score))
+            .collect())
+    }
+
+    /// Number of documents currently stored in the index
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Compress stored embeddings into a product-quantized array, trading
+    /// some accuracy for roughly `subspaces` bytes of RAM per document
+    /// instead of `4 * dimension`. No further `add` calls are possible
+    /// after this.
+    fn compress(&mut self, subspaces: usize) -> PyResult<()> {
+        self.inner
+            .compress(subspaces)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+}
+
 #[cfg(feature = "python")]
 #[pymodule]
 fn embedding_gemma_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
@@ -148,6 +377,8 @@ fn embedding_gemma_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
 
     m.add_class::<PyTextEmbedder>()?;
     m.add_class::<PyQuantizationType>()?;
+    m.add_class::<PyEmbedTask>()?;
     m.add_class::<Reranker>()?;
+    m.add_class::<PySemanticIndex>()?;
     Ok(())
 }
@@ -0,0 +1,353 @@
+//! Product-quantized embedding storage for large corpora
+//!
+//! Splits each embedding into `M` subvectors and replaces each subvector
+//! with the index of its nearest centroid in a per-subspace codebook
+//! (trained with k-means), trading a small amount of accuracy for roughly
+//! `M` bytes per vector (plus the shared codebooks) instead of
+//! `4 * dimension`.
+
+use eyre::Result;
+
+/// Number of centroids trained per subspace
+const CENTROIDS_PER_SUBSPACE: usize = 256;
+
+/// Max k-means iterations when training a subspace's codebook
+const KMEANS_ITERATIONS: usize = 25;
+
+/// Default cap on how many embeddings `quantize` trains codebooks over.
+/// Training (not encoding) is the O(n * k * iterations) part of
+/// quantization, so capping it keeps `compress()` practical even when the
+/// corpus itself holds millions of vectors.
+const DEFAULT_TRAINING_SAMPLE_SIZE: usize = 10_000;
+
+/// A codebook-compressed collection of embeddings
+pub struct QuantizedArray {
+    subspaces: usize,
+    subspace_dim: usize,
+    /// `codebooks[s][c]` is the centroid vector for subspace `s`, code `c`
+    codebooks: Vec<Vec<Vec<f32>>>,
+    /// One code vector (length `subspaces`) per stored embedding
+    codes: Vec<Vec<u8>>,
+}
+
+impl QuantizedArray {
+    /// Train codebooks over a bounded sample of `embeddings` (see
+    /// `DEFAULT_TRAINING_SAMPLE_SIZE`) and encode each one as `subspaces`
+    /// bytes of centroid indices.
+    ///
+    /// `embeddings` must all share the same dimension, and that dimension
+    /// must be evenly divisible by `subspaces`.
+    pub fn quantize(embeddings: &[Vec<f32>], subspaces: usize) -> Result<Self> {
+        Self::quantize_with_sample(embeddings, subspaces, DEFAULT_TRAINING_SAMPLE_SIZE)
+    }
+
+    /// Like `quantize`, but codebook training is capped at `sample_size`
+    /// embeddings instead of the default. K-means is the expensive part of
+    /// quantization (`O(sample_size * centroids * iterations)`), so bounding
+    /// it keeps `compress()` practical even when the corpus holds millions
+    /// of vectors; every embedding is still encoded against the resulting
+    /// codebooks regardless of `sample_size`.
+    pub fn quantize_with_sample(
+        embeddings: &[Vec<f32>],
+        subspaces: usize,
+        sample_size: usize,
+    ) -> Result<Self> {
+        if embeddings.is_empty() {
+            return Err(eyre::eyre!("cannot quantize an empty set of embeddings"));
+        }
+        let dimension = embeddings[0].len();
+        if subspaces == 0 || dimension % subspaces != 0 {
+            return Err(eyre::eyre!(
+                "embedding dimension {} is not divisible by subspaces {}",
+                dimension,
+                subspaces
+            ));
+        }
+        if embeddings.iter().any(|e| e.len() != dimension) {
+            return Err(eyre::eyre!("all embeddings must share the same dimension"));
+        }
+
+        let subspace_dim = dimension / subspaces;
+        let sample = subsample(embeddings, sample_size);
+        let k = CENTROIDS_PER_SUBSPACE.min(sample.len());
+
+        let mut codebooks = Vec::with_capacity(subspaces);
+        for s in 0..subspaces {
+            let start = s * subspace_dim;
+            let end = start + subspace_dim;
+            let subvectors: Vec<Vec<f32>> =
+                sample.iter().map(|e| e[start..end].to_vec()).collect();
+            codebooks.push(kmeans(&subvectors, k, KMEANS_ITERATIONS));
+        }
+
+        let codes = embeddings
+            .iter()
+            .map(|e| encode(e, subspace_dim, &codebooks))
+            .collect();
+
+        Ok(QuantizedArray {
+            subspaces,
+            subspace_dim,
+            codebooks,
+            codes,
+        })
+    }
+
+    /// Number of embeddings stored
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    /// Whether the array holds no embeddings
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// The code (one centroid index per subspace) for the embedding at `index`
+    pub fn code(&self, index: usize) -> &[u8] {
+        &self.codes[index]
+    }
+
+    /// Decompose into raw parts for serialization. See [`QuantizedArray::from_parts`].
+    pub(crate) fn as_parts(&self) -> (usize, usize, &[Vec<Vec<f32>>], &[Vec<u8>]) {
+        (self.subspaces, self.subspace_dim, &self.codebooks, &self.codes)
+    }
+
+    /// Rebuild from parts previously returned by [`QuantizedArray::as_parts`],
+    /// e.g. after reading them back from disk.
+    pub(crate) fn from_parts(
+        subspaces: usize,
+        subspace_dim: usize,
+        codebooks: Vec<Vec<Vec<f32>>>,
+        codes: Vec<Vec<u8>>,
+    ) -> Self {
+        QuantizedArray {
+            subspaces,
+            subspace_dim,
+            codebooks,
+            codes,
+        }
+    }
+
+    /// Reconstruct an approximate embedding from a code, by concatenating
+    /// each subspace's centroid vector
+    pub fn reconstruct(&self, code: &[u8]) -> Vec<f32> {
+        let mut out = Vec::with_capacity(self.subspaces * self.subspace_dim);
+        for (s, &c) in code.iter().enumerate() {
+            out.extend_from_slice(&self.codebooks[s][c as usize]);
+        }
+        out
+    }
+
+    /// Precompute per-subspace distance tables for `query`, to be reused
+    /// across many `DistanceTable::cosine` calls without re-scanning the
+    /// codebooks for every stored code.
+    pub fn distance_table(&self, query: &[f32]) -> Result<DistanceTable> {
+        let expected_dim = self.subspaces * self.subspace_dim;
+        if query.len() != expected_dim {
+            return Err(eyre::eyre!(
+                "query dimension {} does not match index dimension {}",
+                query.len(),
+                expected_dim
+            ));
+        }
+
+        let mut dot_table = Vec::with_capacity(self.subspaces);
+        let mut norm_sq_table = Vec::with_capacity(self.subspaces);
+        for (s, codebook) in self.codebooks.iter().enumerate() {
+            let start = s * self.subspace_dim;
+            let sub_query = &query[start..start + self.subspace_dim];
+            let dots: Vec<f32> = codebook
+                .iter()
+                .map(|centroid| dot(sub_query, centroid))
+                .collect();
+            let norms: Vec<f32> = codebook
+                .iter()
+                .map(|centroid| centroid.iter().map(|x| x * x).sum())
+                .collect();
+            dot_table.push(dots);
+            norm_sq_table.push(norms);
+        }
+
+        let query_norm = query.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+        Ok(DistanceTable {
+            dot_table,
+            norm_sq_table,
+            query_norm,
+        })
+    }
+}
+
+/// Precomputed per-subspace dot-product and norm tables for one query
+/// vector, used to score many codes without touching the raw centroids again.
+pub struct DistanceTable {
+    dot_table: Vec<Vec<f32>>,
+    norm_sq_table: Vec<Vec<f32>>,
+    query_norm: f32,
+}
+
+impl DistanceTable {
+    /// Approximate cosine similarity between the query this table was built
+    /// for and the embedding encoded by `code`, without reconstructing it.
+    pub fn cosine(&self, code: &[u8]) -> f32 {
+        let dot: f32 = code
+            .iter()
+            .enumerate()
+            .map(|(s, &c)| self.dot_table[s][c as usize])
+            .sum();
+        let norm_sq: f32 = code
+            .iter()
+            .enumerate()
+            .map(|(s, &c)| self.norm_sq_table[s][c as usize])
+            .sum();
+
+        if self.query_norm == 0.0 || norm_sq == 0.0 {
+            0.0
+        } else {
+            dot / (self.query_norm * norm_sq.sqrt())
+        }
+    }
+}
+
+fn encode(embedding: &[f32], subspace_dim: usize, codebooks: &[Vec<Vec<f32>>]) -> Vec<u8> {
+    codebooks
+        .iter()
+        .enumerate()
+        .map(|(s, codebook)| {
+            let start = s * subspace_dim;
+            let sub = &embedding[start..start + subspace_dim];
+            nearest_centroid(sub, codebook) as u8
+        })
+        .collect()
+}
+
+fn nearest_centroid(v: &[f32], centroids: &[Vec<f32>]) -> usize {
+    centroids
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, squared_distance(v, c)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Pick up to `sample_size` embeddings, evenly spaced across `embeddings`,
+/// to train codebooks on. Returns every embedding if the corpus is already
+/// at or below `sample_size`.
+fn subsample(embeddings: &[Vec<f32>], sample_size: usize) -> Vec<&Vec<f32>> {
+    let n = embeddings.len();
+    let sample_size = sample_size.min(n).max(1);
+    if sample_size == n {
+        return embeddings.iter().collect();
+    }
+    (0..sample_size)
+        .map(|i| &embeddings[i * n / sample_size])
+        .collect()
+}
+
+/// Lloyd's algorithm k-means, seeded with evenly-spaced samples from `data`
+/// so training is deterministic across runs.
+fn kmeans(data: &[Vec<f32>], k: usize, iterations: usize) -> Vec<Vec<f32>> {
+    let n = data.len();
+    let k = k.min(n).max(1);
+    let dim = data[0].len();
+
+    let mut centroids: Vec<Vec<f32>> = (0..k).map(|i| data[i * n / k].clone()).collect();
+
+    for _ in 0..iterations {
+        let mut sums = vec![vec![0f32; dim]; k];
+        let mut counts = vec![0usize; k];
+
+        for v in data {
+            let c = nearest_centroid(v, &centroids);
+            counts[c] += 1;
+            for (s, x) in sums[c].iter_mut().zip(v) {
+                *s += x;
+            }
+        }
+
+        for c in 0..k {
+            if counts[c] > 0 {
+                for x in sums[c].iter_mut() {
+                    *x /= counts[c] as f32;
+                }
+                centroids[c] = sums[c].clone();
+            }
+        }
+    }
+
+    centroids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_embeddings() -> Vec<Vec<f32>> {
+        (0..8)
+            .map(|i| (0..8).map(|d| (i * 8 + d) as f32).collect())
+            .collect()
+    }
+
+    #[test]
+    fn quantize_rejects_dimension_not_divisible_by_subspaces() {
+        let embeddings = sample_embeddings();
+        let err = QuantizedArray::quantize(&embeddings, 3).unwrap_err();
+        assert!(err.to_string().contains("not divisible"));
+    }
+
+    #[test]
+    fn reconstruct_is_exact_when_codebook_covers_every_point() {
+        // With as many centroids as training points, k-means should settle
+        // with one centroid per point, so reconstruction is lossless.
+        let embeddings = sample_embeddings();
+        let quantized = QuantizedArray::quantize(&embeddings, 2).unwrap();
+
+        for (i, original) in embeddings.iter().enumerate() {
+            let code = quantized.code(i).to_vec();
+            let reconstructed = quantized.reconstruct(&code);
+            assert_eq!(&reconstructed, original);
+        }
+    }
+
+    #[test]
+    fn quantize_with_sample_trains_on_a_bounded_subset_but_encodes_every_embedding() {
+        let embeddings = sample_embeddings();
+        // Cap training well below the corpus size; every embedding should
+        // still get a code even though only a handful were used to train.
+        let quantized = QuantizedArray::quantize_with_sample(&embeddings, 2, 3).unwrap();
+
+        assert_eq!(quantized.len(), embeddings.len());
+        for i in 0..embeddings.len() {
+            assert_eq!(quantized.code(i).len(), 2);
+        }
+    }
+
+    #[test]
+    fn distance_table_cosine_matches_brute_force_for_exact_codebook() {
+        let embeddings = sample_embeddings();
+        let quantized = QuantizedArray::quantize(&embeddings, 2).unwrap();
+
+        let query = vec![1.0f32; 8];
+        let table = quantized.distance_table(&query).unwrap();
+
+        for (i, embedding) in embeddings.iter().enumerate() {
+            let expected = dot(&query, embedding)
+                / (dot(&query, &query).sqrt() * dot(embedding, embedding).sqrt());
+            let actual = table.cosine(quantized.code(i));
+            assert!(
+                (expected - actual).abs() < 1e-4,
+                "expected {expected}, got {actual}"
+            );
+        }
+    }
+}
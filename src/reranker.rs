@@ -1,9 +1,28 @@
 use eyre::Result;
-use fastembed::{RerankInitOptions, RerankResult, RerankerModel, TextRerank};
+use fastembed::{RerankInitOptions, RerankerModel, TextRerank};
+
+/// Mean/sigma of a shifted sigmoid used to map raw reranker scores into a
+/// stable, cross-query-comparable `0..1` relevance value.
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreCalibration {
+    pub mean: f32,
+    pub sigma: f32,
+}
+
+/// A reranked document, with both the model's raw score and, if a
+/// `ScoreCalibration` is configured, a normalized `0..1` relevance score.
+#[derive(Debug, Clone)]
+pub struct RerankedResult {
+    pub document: Option<String>,
+    pub index: usize,
+    pub score: f32,
+    pub normalized_score: Option<f32>,
+}
 
 /// Reranker wrapper around FastEmbed
 pub struct TextReranker {
     model: TextRerank,
+    calibration: Option<ScoreCalibration>,
 }
 
 impl TextReranker {
@@ -16,11 +35,40 @@ impl TextReranker {
         )
         .map_err(|e| eyre::eyre!("Failed to load reranker model: {}", e))?;
 
-        Ok(TextReranker { model })
+        Ok(TextReranker {
+            model,
+            calibration: None,
+        })
+    }
+
+    /// Configure the `mean`/`sigma` of the shifted sigmoid used to normalize
+    /// raw scores into a `0..1` relevance value. Use `estimate_calibration`
+    /// to derive these from a sample of scored query/document pairs.
+    pub fn with_calibration(mut self, mean: f32, sigma: f32) -> Self {
+        self.calibration = Some(ScoreCalibration { mean, sigma });
+        self
+    }
+
+    /// Estimate `mean` and `sigma` (mean and population standard deviation) from
+    /// a sample of raw reranker scores, suitable for `with_calibration`.
+    pub fn estimate_calibration(scores: &[f32]) -> Result<(f32, f32)> {
+        if scores.is_empty() {
+            return Err(eyre::eyre!("cannot estimate calibration from an empty sample"));
+        }
+
+        let mean = scores.iter().sum::<f32>() / scores.len() as f32;
+        let variance =
+            scores.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / scores.len() as f32;
+
+        Ok((mean, variance.sqrt()))
     }
 
     /// Rerank a list of documents against a query
-    pub fn rerank(&mut self, query: String, documents: Vec<String>) -> Result<Vec<RerankResult>> {
+    ///
+    /// Each result carries the model's raw score, and, if this reranker was
+    /// built with `with_calibration`, a normalized `0..1` score so callers
+    /// can apply a meaningful cutoff across different queries.
+    pub fn rerank(&mut self, query: String, documents: Vec<String>) -> Result<Vec<RerankedResult>> {
         if documents.is_empty() {
             return Ok(vec![]);
         }
@@ -30,6 +78,65 @@ impl TextReranker {
             .rerank(query, documents, true, None)
             .map_err(|e| eyre::eyre!("Reranking failed: {}", e))?;
 
+        let results = results
+            .into_iter()
+            .map(|r| {
+                let normalized_score = self
+                    .calibration
+                    .map(|c| shifted_sigmoid(r.score, c.mean, c.sigma));
+                RerankedResult {
+                    document: r.document,
+                    index: r.index,
+                    score: r.score,
+                    normalized_score,
+                }
+            })
+            .collect();
+
         Ok(results)
     }
 }
+
+/// Shifted sigmoid: maps a raw score `s` through `1 / (1 + exp(-(s - mean) / sigma))`
+fn shifted_sigmoid(score: f32, mean: f32, sigma: f32) -> f32 {
+    if sigma == 0.0 {
+        return if score >= mean { 1.0 } else { 0.0 };
+    }
+    1.0 / (1.0 + (-(score - mean) / sigma).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifted_sigmoid_is_half_at_the_mean() {
+        assert!((shifted_sigmoid(5.0, 5.0, 2.0) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn shifted_sigmoid_stays_within_unit_range() {
+        for score in [-100.0, -1.0, 0.0, 1.0, 100.0] {
+            let normalized = shifted_sigmoid(score, 0.0, 1.0);
+            assert!((0.0..=1.0).contains(&normalized));
+        }
+    }
+
+    #[test]
+    fn shifted_sigmoid_handles_zero_sigma_as_a_step_function() {
+        assert_eq!(shifted_sigmoid(5.0, 5.0, 0.0), 1.0);
+        assert_eq!(shifted_sigmoid(4.999, 5.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn estimate_calibration_computes_mean_and_stddev() {
+        let (mean, sigma) = TextReranker::estimate_calibration(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]).unwrap();
+        assert!((mean - 5.0).abs() < 1e-4);
+        assert!((sigma - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn estimate_calibration_rejects_empty_sample() {
+        assert!(TextReranker::estimate_calibration(&[]).is_err());
+    }
+}
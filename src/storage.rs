@@ -0,0 +1,271 @@
+//! On-disk persistence for embeddings, in a simple self-describing chunked
+//! binary format (magic bytes, header, row-major vector data, id table)
+//! similar in spirit to finalfusion's chunked storage layout.
+
+use crate::quantize::QuantizedArray;
+use eyre::Result;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC_DENSE: [u8; 4] = *b"EMBG";
+const MAGIC_QUANTIZED: [u8; 4] = *b"EMBQ";
+const FORMAT_VERSION: u8 = 1;
+
+/// Save `ids` and their corresponding `vectors` to `path` in row-major
+/// binary form: `EMBG` magic, a header with row count and dimension, the
+/// raw f32 data, and an id/string table.
+pub fn save_embeddings(path: impl AsRef<Path>, ids: &[String], vectors: &[Vec<f32>]) -> Result<()> {
+    if ids.len() != vectors.len() {
+        return Err(eyre::eyre!(
+            "ids ({}) and vectors ({}) must have the same length",
+            ids.len(),
+            vectors.len()
+        ));
+    }
+
+    let dimension = vectors.first().map(|v| v.len()).unwrap_or(0);
+    if vectors.iter().any(|v| v.len() != dimension) {
+        return Err(eyre::eyre!("all vectors must share the same dimension"));
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&MAGIC_DENSE)?;
+    writer.write_all(&[FORMAT_VERSION])?;
+    writer.write_all(&(vectors.len() as u64).to_le_bytes())?;
+    writer.write_all(&(dimension as u64).to_le_bytes())?;
+
+    for vector in vectors {
+        for x in vector {
+            writer.write_all(&x.to_le_bytes())?;
+        }
+    }
+
+    write_id_table(&mut writer, ids)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Load ids and vectors previously written by `save_embeddings`.
+pub fn load_embeddings(path: impl AsRef<Path>) -> Result<(Vec<String>, Vec<Vec<f32>>)> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC_DENSE {
+        return Err(eyre::eyre!("not an EMBG embeddings file"));
+    }
+    let _version = read_u8(&mut reader)?;
+    let row_count = read_u64(&mut reader)? as usize;
+    let dimension = read_u64(&mut reader)? as usize;
+
+    let mut vectors = Vec::with_capacity(row_count);
+    for _ in 0..row_count {
+        let mut row = Vec::with_capacity(dimension);
+        for _ in 0..dimension {
+            row.push(read_f32(&mut reader)?);
+        }
+        vectors.push(row);
+    }
+
+    let ids = read_id_table(&mut reader, row_count)?;
+    Ok((ids, vectors))
+}
+
+/// Save `ids` alongside a product-quantized array, round-tripping the
+/// trained codebooks and per-vector codes so `load_quantized` can
+/// reconstruct an equivalent `QuantizedArray` without re-training.
+pub fn save_quantized(path: impl AsRef<Path>, ids: &[String], quantized: &QuantizedArray) -> Result<()> {
+    if ids.len() != quantized.len() {
+        return Err(eyre::eyre!(
+            "ids ({}) and quantized rows ({}) must have the same length",
+            ids.len(),
+            quantized.len()
+        ));
+    }
+
+    let (subspaces, subspace_dim, codebooks, codes) = quantized.as_parts();
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&MAGIC_QUANTIZED)?;
+    writer.write_all(&[FORMAT_VERSION])?;
+    writer.write_all(&(codes.len() as u64).to_le_bytes())?;
+    writer.write_all(&(subspaces as u64).to_le_bytes())?;
+    writer.write_all(&(subspace_dim as u64).to_le_bytes())?;
+
+    for codebook in codebooks {
+        writer.write_all(&(codebook.len() as u64).to_le_bytes())?;
+        for centroid in codebook {
+            for x in centroid {
+                writer.write_all(&x.to_le_bytes())?;
+            }
+        }
+    }
+
+    for code in codes {
+        writer.write_all(code)?;
+    }
+
+    write_id_table(&mut writer, ids)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Load ids and a `QuantizedArray` previously written by `save_quantized`.
+pub fn load_quantized(path: impl AsRef<Path>) -> Result<(Vec<String>, QuantizedArray)> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC_QUANTIZED {
+        return Err(eyre::eyre!("not an EMBQ quantized embeddings file"));
+    }
+    let _version = read_u8(&mut reader)?;
+    let row_count = read_u64(&mut reader)? as usize;
+    let subspaces = read_u64(&mut reader)? as usize;
+    let subspace_dim = read_u64(&mut reader)? as usize;
+
+    let mut codebooks = Vec::with_capacity(subspaces);
+    for _ in 0..subspaces {
+        let k = read_u64(&mut reader)? as usize;
+        let mut codebook = Vec::with_capacity(k);
+        for _ in 0..k {
+            let mut centroid = Vec::with_capacity(subspace_dim);
+            for _ in 0..subspace_dim {
+                centroid.push(read_f32(&mut reader)?);
+            }
+            codebook.push(centroid);
+        }
+        codebooks.push(codebook);
+    }
+
+    let mut codes = Vec::with_capacity(row_count);
+    for _ in 0..row_count {
+        let mut code = vec![0u8; subspaces];
+        reader.read_exact(&mut code)?;
+        codes.push(code);
+    }
+
+    let ids = read_id_table(&mut reader, row_count)?;
+    let quantized = QuantizedArray::from_parts(subspaces, subspace_dim, codebooks, codes);
+    Ok((ids, quantized))
+}
+
+fn write_id_table(writer: &mut impl Write, ids: &[String]) -> Result<()> {
+    for id in ids {
+        let bytes = id.as_bytes();
+        writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        writer.write_all(bytes)?;
+    }
+    Ok(())
+}
+
+fn read_id_table(reader: &mut impl Read, count: usize) -> Result<Vec<String>> {
+    let mut ids = Vec::with_capacity(count);
+    for _ in 0..count {
+        let len = read_u32(reader)? as usize;
+        let mut buf = vec![0u8; len];
+        reader.read_exact(&mut buf)?;
+        ids.push(String::from_utf8(buf).map_err(|e| eyre::eyre!("invalid id string: {}", e))?);
+    }
+    Ok(ids)
+}
+
+fn read_u8(reader: &mut impl Read) -> Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_f32(reader: &mut impl Read) -> Result<f32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(f32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A path under the OS temp dir, unique per test invocation, removed
+    /// when the guard drops.
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = std::env::temp_dir().join(format!(
+                "embedding-gemma-rs-test-{}-{}-{}",
+                std::process::id(),
+                id,
+                name
+            ));
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn save_and_load_embeddings_round_trip() {
+        let path = TempFile::new("dense.bin");
+        let ids = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let vectors = vec![
+            vec![1.0, 2.0, 3.0],
+            vec![4.0, 5.0, 6.0],
+            vec![-1.0, 0.0, 0.5],
+        ];
+
+        save_embeddings(&path.0, &ids, &vectors).unwrap();
+        let (loaded_ids, loaded_vectors) = load_embeddings(&path.0).unwrap();
+
+        assert_eq!(loaded_ids, ids);
+        assert_eq!(loaded_vectors, vectors);
+    }
+
+    #[test]
+    fn load_embeddings_rejects_wrong_magic() {
+        let path = TempFile::new("not-embeddings.bin");
+        std::fs::write(&path.0, b"nope").unwrap();
+        assert!(load_embeddings(&path.0).is_err());
+    }
+
+    #[test]
+    fn save_and_load_quantized_round_trip() {
+        let path = TempFile::new("quantized.bin");
+        let ids = vec!["a".to_string(), "b".to_string()];
+        let embeddings = vec![vec![1.0, 2.0, 3.0, 4.0], vec![5.0, 6.0, 7.0, 8.0]];
+        let quantized = QuantizedArray::quantize(&embeddings, 2).unwrap();
+
+        save_quantized(&path.0, &ids, &quantized).unwrap();
+        let (loaded_ids, loaded_quantized) = load_quantized(&path.0).unwrap();
+
+        assert_eq!(loaded_ids, ids);
+        assert_eq!(loaded_quantized.len(), quantized.len());
+        for i in 0..embeddings.len() {
+            assert_eq!(loaded_quantized.code(i), quantized.code(i));
+            assert_eq!(
+                loaded_quantized.reconstruct(loaded_quantized.code(i)),
+                quantized.reconstruct(quantized.code(i))
+            );
+        }
+    }
+}